@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+
+/// Extension used for the attribute sidecar file stored next to a key's
+/// value file.
+pub(crate) const EXTENSION: &str = "attr";
+
+/// The attributes attached to a single key, keyed by a small numeric id.
+pub(crate) type Attrs = HashMap<u8, Vec<u8>>;
+
+/// Encodes a set of attributes as a compact length-prefixed blob:
+/// `(id: u8, len: u32 LE, bytes)*`.
+pub(crate) fn encode(attrs: &Attrs) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    for (id, value) in attrs {
+        buf.push(*id);
+        buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+
+    buf
+}
+
+/// Decodes a blob produced by [`encode`].
+pub(crate) fn decode(data: &[u8]) -> Result<Attrs> {
+    let mut attrs = Attrs::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let id = *data
+            .get(pos)
+            .ok_or_else(|| eyre!("Corrupt attribute data: truncated id"))?;
+        pos += 1;
+
+        let len_bytes: [u8; 4] = data
+            .get(pos..pos + 4)
+            .ok_or_else(|| eyre!("Corrupt attribute data: truncated length"))?
+            .try_into()
+            .unwrap();
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        pos += 4;
+
+        let value = data
+            .get(pos..pos + len)
+            .ok_or_else(|| eyre!("Corrupt attribute data: truncated value"))?
+            .to_vec();
+        pos += len;
+
+        attrs.insert(id, value);
+    }
+
+    Ok(attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut attrs = Attrs::new();
+        attrs.insert(1, b"text/plain".to_vec());
+        attrs.insert(2, vec![]);
+
+        assert_eq!(decode(&encode(&attrs)).unwrap(), attrs);
+    }
+}
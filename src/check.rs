@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+/// The result of [`crate::Flatfs::check`] (or [`crate::Flatfs::repair`],
+/// which returns the issues it fixed).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Data files that live in a different shard directory than the one
+    /// their key currently hashes to, e.g. after a change to the sharding
+    /// scheme.
+    pub misfiled: Vec<Misfiled>,
+    /// Leftover temp files from a write that crashed between creating the
+    /// temp file and renaming it into place.
+    pub orphan_temp_files: Vec<PathBuf>,
+    /// Entries whose metadata couldn't be read at all.
+    pub unreadable: Vec<Unreadable>,
+}
+
+impl Report {
+    /// Whether the store had no issues at all.
+    pub fn is_clean(&self) -> bool {
+        self.misfiled.is_empty() && self.orphan_temp_files.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// A data file found in the wrong shard directory for its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misfiled {
+    pub key: String,
+    pub found_at: PathBuf,
+    pub expected_at: PathBuf,
+}
+
+/// An entry whose metadata could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unreadable {
+    pub path: PathBuf,
+    pub error: String,
+}
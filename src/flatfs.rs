@@ -1,19 +1,42 @@
 use std::{
-    fs, io,
+    fs,
+    io::Write,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::channel,
+        Arc, Mutex,
+    },
+    thread,
     time::Duration,
 };
 
 use eyre::{eyre, Result, WrapErr};
+use tempfile::Builder as TempFileBuilder;
 
-use crate::shard::{self, Shard};
+use crate::{
+    attr,
+    check::{Misfiled, Report, Unreadable},
+    shard::{self, Shard},
+    usage,
+    watch::{Event, EventKind, WatchStream, Watchers},
+};
 
 pub struct Flatfs {
     path: PathBuf,
     shard: Shard,
+    sync_mode: SyncMode,
+    watchers: Arc<Mutex<Watchers>>,
+    usage_total: Arc<AtomicU64>,
+    usage_approximate: Arc<AtomicBool>,
+    usage_dirty_ops: Arc<AtomicU64>,
 }
 
-const EXTENSION: &str = "data";
+pub(crate) const EXTENSION: &str = "data";
+
+/// Prefix of temp files created while writing, so orphans left behind by a
+/// crash can be recognized by [`Flatfs::cleanup_temp`].
+const TEMP_FILE_PREFIX: &str = ".flatfs-tmp-";
 
 /// Timeout (in ms) for a backoff on retrying operations.
 const RETRY_DELAY: u64 = 200;
@@ -21,6 +44,37 @@ const RETRY_DELAY: u64 = 200;
 /// The maximum number of retries that will be attempted.
 const RETRY_ATTEMPTS: usize = 6;
 
+/// How long `open` waits for a full disk-usage recount to finish before
+/// giving up and letting it continue in the background, marked
+/// `approximate` in the meantime.
+const USAGE_RECOUNT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many `put`/`del` calls accumulate between persisting the disk-usage
+/// counter, instead of flushing it to disk on every single mutation.
+const USAGE_FLUSH_INTERVAL: u64 = 32;
+
+/// Controls how aggressively `put` flushes data to disk before considering
+/// a write durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Never fsync. Fastest, but a crash can lose or corrupt the most
+    /// recent writes.
+    None,
+    /// Fsync the file's contents before renaming it into place, but don't
+    /// fsync the containing directory.
+    Data,
+    /// Fsync the file's contents before renaming, and fsync the containing
+    /// directory afterwards so the rename itself is durable. This is the
+    /// default.
+    Full,
+}
+
+impl Default for SyncMode {
+    fn default() -> Self {
+        SyncMode::Full
+    }
+}
+
 impl Flatfs {
     /// Creates or opens an existing store at the provided path as the root.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -29,10 +83,16 @@ impl Flatfs {
 
     /// Creates or opens an existing store at the provided path as the root.
     pub fn with_shard<P: AsRef<Path>>(path: P, shard: Shard) -> Result<Self> {
+        Self::with_options(path, shard, SyncMode::default())
+    }
+
+    /// Creates or opens an existing store at the provided path as the root,
+    /// with explicit control over fsync behavior.
+    pub fn with_options<P: AsRef<Path>>(path: P, shard: Shard, sync_mode: SyncMode) -> Result<Self> {
         if path.as_ref().exists() && path.as_ref().join(shard::FILE_NAME).exists() {
-            Self::open(path, shard)
+            Self::open(path, shard, sync_mode)
         } else {
-            Self::create(path, shard)
+            Self::create(path, shard, sync_mode)
         }
     }
 
@@ -41,32 +101,123 @@ impl Flatfs {
         ensure_valid_key(key)?;
         let filepath = self.as_path(key);
         let parent_dir = filepath.parent().unwrap();
+        let old_size = filepath.metadata().map(|m| m.len()).unwrap_or(0);
 
-        // Make sure the sharding directory exists.
+        // Make sure the sharding directory (and any missing parents) exist,
+        // tolerating races with other writers creating the same shard.
         if !parent_dir.exists() {
-            if let Err(err) = retry(|| fs::create_dir(&parent_dir)) {
-                // Directory got already created, that's fine.
-                if err.kind() != io::ErrorKind::AlreadyExists {
-                    return Err(err)
-                        .wrap_err_with(|| format!("Failed to create {:?}", filepath.parent()));
-                }
-            }
+            shard::create_dir_all_raced(parent_dir)
+                .wrap_err_with(|| format!("Failed to create {parent_dir:?}"))?;
         }
 
-        // Write to temp location
-        let temp_filepath = filepath.with_extension(".temp");
+        // Write to a unique temp file in the target shard directory. If we
+        // return before persisting it, it's removed on drop.
+        let mut temp_file = retry(|| {
+            TempFileBuilder::new()
+                .prefix(TEMP_FILE_PREFIX)
+                .tempfile_in(parent_dir)
+        })
+        .wrap_err_with(|| format!("Failed to create temp file in {parent_dir:?}"))?;
+
         let value = value.as_ref();
-        retry(|| fs::write(&temp_filepath, value))
-            .wrap_err_with(|| format!("Failed to write {:?}", temp_filepath))?;
+        temp_file
+            .write_all(value)
+            .wrap_err_with(|| format!("Failed to write {:?}", temp_file.path()))?;
 
-        // Rename after successfull write
-        retry(|| fs::rename(&temp_filepath, &filepath)).wrap_err_with(|| {
-            format!("Failed to reaname: {:?} -> {:?}", temp_filepath, filepath)
-        })?;
+        if self.sync_mode != SyncMode::None {
+            temp_file
+                .as_file()
+                .sync_all()
+                .wrap_err_with(|| format!("Failed to fsync {:?}", temp_file.path()))?;
+        }
+
+        // Persist (rename) and notify under the watchers lock, so a
+        // concurrent `watch_existing`'s snapshot-then-subscribe can never
+        // race with this mutation: either it finishes (and its snapshot
+        // already reflects this key) before we acquire the lock, or it
+        // hasn't started yet and will receive this key live.
+        {
+            let mut watchers = self.watchers.lock().unwrap();
+
+            // This disarms the temp file's on-drop cleanup.
+            temp_file.persist(&filepath).map_err(|err| {
+                eyre!(
+                    "Failed to persist {:?} -> {:?}: {}",
+                    err.file.path(),
+                    filepath,
+                    err.error
+                )
+            })?;
+
+            if self.sync_mode == SyncMode::Full {
+                sync_dir(parent_dir)
+                    .wrap_err_with(|| format!("Failed to fsync directory {parent_dir:?}"))?;
+            }
+
+            watchers.notify(key, EventKind::Added);
+        }
+
+        self.note_usage_delta(value.len() as u64, old_size);
 
         Ok(())
     }
 
+    /// Scans every shard directory and removes leftover temp files from
+    /// writes that crashed between creating the temp file and renaming it
+    /// into place. Returns the number of files removed.
+    pub fn cleanup_temp(&self) -> Result<usize> {
+        let mut removed = 0;
+        remove_orphan_temp_files(&self.path, &mut removed)?;
+        Ok(removed)
+    }
+
+    /// Returns the total size, in bytes, of every value and attribute
+    /// currently stored. Reads the in-memory running total in O(1); it may
+    /// be briefly approximate right after opening a store that didn't have
+    /// a persisted counter yet (see [`Flatfs::recalculate_disk_usage`]).
+    pub fn disk_usage(&self) -> u64 {
+        self.usage_total.load(Ordering::SeqCst)
+    }
+
+    /// Forces an exact recount of disk usage by walking every shard
+    /// directory, and persists the result as the new running total.
+    pub fn recalculate_disk_usage(&self) -> Result<u64> {
+        let total = usage::walk_total(&self.path)?;
+        self.usage_total.store(total, Ordering::SeqCst);
+        self.usage_approximate.store(false, Ordering::SeqCst);
+        usage::write(&self.path.join(usage::FILE_NAME), total, false)?;
+        Ok(total)
+    }
+
+    fn flush_disk_usage(&self) {
+        let _ = usage::write(
+            &self.path.join(usage::FILE_NAME),
+            self.usage_total.load(Ordering::SeqCst),
+            self.usage_approximate.load(Ordering::SeqCst),
+        );
+    }
+
+    /// Records a change in disk usage from a `put`/`del`, flushing the
+    /// persisted counter every [`USAGE_FLUSH_INTERVAL`] mutations rather
+    /// than on every single one.
+    fn note_usage_delta(&self, added: u64, removed: u64) {
+        self.usage_total.fetch_add(added, Ordering::SeqCst);
+        // Saturating: if the store was just opened without a `DISK_USAGE`
+        // file, the counter starts at 0 while the bounded background
+        // recount is still running, and a `del`/overwriting `put` in that
+        // window must not wrap it around to near `u64::MAX`.
+        let _ = self
+            .usage_total
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                Some(current.saturating_sub(removed))
+            });
+
+        if self.usage_dirty_ops.fetch_add(1, Ordering::SeqCst) + 1 >= USAGE_FLUSH_INTERVAL {
+            self.usage_dirty_ops.store(0, Ordering::SeqCst);
+            self.flush_disk_usage();
+        }
+    }
+
     /// Retrieves the value under the given key.
     pub fn get(&self, key: &str) -> Result<Vec<u8>> {
         ensure_valid_key(key)?;
@@ -94,14 +245,241 @@ impl Flatfs {
     pub fn del(&self, key: &str) -> Result<()> {
         ensure_valid_key(key)?;
         let filepath = self.as_path(key);
+        let size = filepath.metadata().map(|m| m.len()).unwrap_or(0);
+
+        // Removal and notification happen under the watchers lock for the
+        // same reason as in `put`: it makes `watch_existing`'s
+        // snapshot-then-subscribe race-free.
+        {
+            let mut watchers = self.watchers.lock().unwrap();
+
+            retry(|| fs::remove_file(&filepath))
+                .wrap_err_with(|| format!("Failed to remove {:?}", filepath))?;
+
+            watchers.notify(key, EventKind::Removed);
+        }
+
+        let attr_filepath = self.as_attr_path(key);
+        let attr_size = attr_filepath.metadata().map(|m| m.len()).unwrap_or(0);
+        if attr_filepath.exists() {
+            retry(|| fs::remove_file(&attr_filepath))
+                .wrap_err_with(|| format!("Failed to remove {:?}", attr_filepath))?;
+        }
+
+        self.note_usage_delta(0, size + attr_size);
+
+        Ok(())
+    }
+
+    /// Stores a single attribute under the given key, alongside its value.
+    ///
+    /// Attributes are kept in a sidecar file next to the value in the same
+    /// shard directory, so they share its atomic-rename write discipline.
+    pub fn put_attr(&self, key: &str, attr_id: u8, value: &[u8]) -> Result<()> {
+        ensure_valid_key(key)?;
+
+        let mut attrs = self.read_attrs(key)?.unwrap_or_default();
+        attrs.insert(attr_id, value.to_vec());
+        self.write_attrs(key, &attrs)
+    }
+
+    /// Retrieves a single attribute stored under the given key, if any.
+    pub fn get_attr(&self, key: &str, attr_id: u8) -> Result<Option<Vec<u8>>> {
+        ensure_valid_key(key)?;
+
+        Ok(self
+            .read_attrs(key)?
+            .and_then(|attrs| attrs.get(&attr_id).cloned()))
+    }
+
+    /// Removes a single attribute from the given key. Removing the last
+    /// attribute deletes the sidecar file entirely.
+    pub fn del_attr(&self, key: &str, attr_id: u8) -> Result<()> {
+        ensure_valid_key(key)?;
+
+        let Some(mut attrs) = self.read_attrs(key)? else {
+            return Ok(());
+        };
+
+        if attrs.remove(&attr_id).is_none() {
+            return Ok(());
+        }
+
+        if attrs.is_empty() {
+            let attr_filepath = self.as_attr_path(key);
+            let old_size = attr_filepath.metadata().map(|m| m.len()).unwrap_or(0);
+
+            retry(|| fs::remove_file(&attr_filepath))
+                .wrap_err_with(|| format!("Failed to remove {:?}", attr_filepath))?;
+
+            self.note_usage_delta(0, old_size);
+
+            Ok(())
+        } else {
+            self.write_attrs(key, &attrs)
+        }
+    }
+
+    fn read_attrs(&self, key: &str) -> Result<Option<attr::Attrs>> {
+        let attr_filepath = self.as_attr_path(key);
+        if !attr_filepath.exists() {
+            return Ok(None);
+        }
+
+        let data = retry(|| fs::read(&attr_filepath))
+            .wrap_err_with(|| format!("Failed to read {:?}", attr_filepath))?;
+
+        Ok(Some(attr::decode(&data)?))
+    }
+
+    fn write_attrs(&self, key: &str, attrs: &attr::Attrs) -> Result<()> {
+        let attr_filepath = self.as_attr_path(key);
+        let parent_dir = attr_filepath.parent().unwrap();
+        let old_size = attr_filepath.metadata().map(|m| m.len()).unwrap_or(0);
+
+        if !parent_dir.exists() {
+            shard::create_dir_all_raced(parent_dir)
+                .wrap_err_with(|| format!("Failed to create {parent_dir:?}"))?;
+        }
+
+        // Same unique-temp-file-plus-fsync discipline as `put`, so a crash
+        // mid-write leaves a recognizable orphan (cleaned up by
+        // `cleanup_temp`/`check`) rather than corrupting the sidecar.
+        let mut temp_file = retry(|| {
+            TempFileBuilder::new()
+                .prefix(TEMP_FILE_PREFIX)
+                .tempfile_in(parent_dir)
+        })
+        .wrap_err_with(|| format!("Failed to create temp file in {parent_dir:?}"))?;
+
+        let data = attr::encode(attrs);
+        temp_file
+            .write_all(&data)
+            .wrap_err_with(|| format!("Failed to write {:?}", temp_file.path()))?;
 
-        retry(|| fs::remove_file(&filepath))
-            .wrap_err_with(|| format!("Failed to remove {:?}", filepath))?;
+        if self.sync_mode != SyncMode::None {
+            temp_file
+                .as_file()
+                .sync_all()
+                .wrap_err_with(|| format!("Failed to fsync {:?}", temp_file.path()))?;
+        }
+
+        temp_file.persist(&attr_filepath).map_err(|err| {
+            eyre!(
+                "Failed to persist {:?} -> {:?}: {}",
+                err.file.path(),
+                attr_filepath,
+                err.error
+            )
+        })?;
+
+        if self.sync_mode == SyncMode::Full {
+            sync_dir(parent_dir)
+                .wrap_err_with(|| format!("Failed to fsync directory {parent_dir:?}"))?;
+        }
+
+        self.note_usage_delta(data.len() as u64, old_size);
 
         Ok(())
     }
 
-    fn create<P: AsRef<Path>>(path: P, shard: Shard) -> Result<Self> {
+    fn as_attr_path(&self, key: &str) -> PathBuf {
+        let mut p = self.path.join(self.shard.dir(key)).join(key);
+        p.set_extension(attr::EXTENSION);
+        p
+    }
+
+    /// Subscribes to live `put`/`del` mutations. The returned stream only
+    /// carries events that occur after this call returns; it does not
+    /// include keys that already exist in the store.
+    pub fn watch(&self) -> WatchStream {
+        self.watchers.lock().unwrap().subscribe()
+    }
+
+    /// Subscribes to mutations the same way as [`Flatfs::watch`], but first
+    /// enumerates every key currently in the store as a synthetic `Added`
+    /// event. The key walk and the subscription are both performed while
+    /// holding the watchers lock, and `put`/`del` only persist their
+    /// mutation and notify while holding that same lock, so a mutation
+    /// racing with this call can never be missed or double-delivered: it
+    /// either completes (and is reflected in the snapshot) before this
+    /// call acquires the lock, or it blocks until this call is done and is
+    /// then delivered live to the newly registered subscriber.
+    ///
+    /// The race-freedom comes at a cost: the lock is held across the
+    /// entire key walk, so every `put`/`del` is blocked behind a full-store
+    /// scan for the duration of this call. On a store with millions of
+    /// files that scan is not instantaneous.
+    pub fn watch_existing(&self) -> Result<WatchStream> {
+        let mut watchers = self.watchers.lock().unwrap();
+        let keys = self.keys()?;
+        let (tx, rx) = channel();
+
+        for key in keys {
+            // The receiver was just created above, so this can't fail.
+            let _ = tx.send(Event {
+                key,
+                kind: EventKind::Added,
+            });
+        }
+
+        watchers.register(tx);
+
+        Ok(rx)
+    }
+
+    /// Walks every shard directory and validates structural invariants:
+    /// that each data file (and attribute sidecar) lives in the shard
+    /// directory its key currently hashes to, that no orphaned temp files
+    /// remain, and that every entry is readable.
+    pub fn check(&self) -> Result<Report> {
+        let mut report = Report::default();
+        collect_report(&self.path, &self.path, &self.shard, &mut report)?;
+        Ok(report)
+    }
+
+    /// Runs [`Flatfs::check`] and fixes what it finds: misfiled values and
+    /// their attribute sidecars are each relocated into their correct
+    /// shard directory via the same atomic rename `put` uses, and
+    /// confirmed orphan temp files are deleted. Returns the report
+    /// describing what was found (and has now been fixed).
+    pub fn repair(&self) -> Result<Report> {
+        let report = self.check()?;
+
+        for misfiled in &report.misfiled {
+            if let Some(parent) = misfiled.expected_at.parent() {
+                if !parent.exists() {
+                    shard::create_dir_all_raced(parent)
+                        .wrap_err_with(|| format!("Failed to create {parent:?}"))?;
+                }
+            }
+
+            retry(|| fs::rename(&misfiled.found_at, &misfiled.expected_at)).wrap_err_with(
+                || {
+                    format!(
+                        "Failed to relocate {:?} -> {:?}",
+                        misfiled.found_at, misfiled.expected_at
+                    )
+                },
+            )?;
+        }
+
+        for orphan in &report.orphan_temp_files {
+            retry(|| fs::remove_file(orphan))
+                .wrap_err_with(|| format!("Failed to remove {orphan:?}"))?;
+        }
+
+        Ok(report)
+    }
+
+    /// Lists every key currently stored, by walking the shard directories.
+    fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        collect_keys(&self.path, &mut keys)?;
+        Ok(keys)
+    }
+
+    fn create<P: AsRef<Path>>(path: P, shard: Shard, sync_mode: SyncMode) -> Result<Self> {
         fs::create_dir_all(&path)
             .wrap_err_with(|| format!("Failed to create {:?}", path.as_ref()))?;
 
@@ -109,10 +487,10 @@ impl Flatfs {
             .write_to_file(&path)
             .wrap_err("Failed to write shard to file")?;
 
-        Self::open(path, shard)
+        Self::open(path, shard, sync_mode)
     }
 
-    fn open<P: AsRef<Path>>(path: P, shard: Shard) -> Result<Self> {
+    fn open<P: AsRef<Path>>(path: P, shard: Shard, sync_mode: SyncMode) -> Result<Self> {
         let existing_shard = Shard::from_file(&path)?;
         if shard != existing_shard {
             return Err(eyre!(
@@ -122,10 +500,69 @@ impl Flatfs {
             ));
         }
 
-        Ok(Flatfs {
-            path: path.as_ref().to_path_buf(),
+        let path = path.as_ref().to_path_buf();
+        let (total, approximate) = match usage::read(&path.join(usage::FILE_NAME))? {
+            Some(counter) => counter,
+            None => (0, true),
+        };
+
+        let flatfs = Flatfs {
+            path,
             shard,
-        })
+            sync_mode,
+            watchers: Arc::new(Mutex::new(Watchers::default())),
+            usage_total: Arc::new(AtomicU64::new(total)),
+            usage_approximate: Arc::new(AtomicBool::new(approximate)),
+            usage_dirty_ops: Arc::new(AtomicU64::new(0)),
+        };
+
+        if approximate {
+            flatfs.recount_disk_usage_bounded()?;
+        }
+
+        Ok(flatfs)
+    }
+
+    /// Kicks off a full disk-usage recount on a background thread, waiting
+    /// up to [`USAGE_RECOUNT_TIMEOUT`] for it to finish. If it finishes in
+    /// time, the exact total is stored and persisted right away. If not,
+    /// the counter stays marked `approximate` and the background thread
+    /// updates it (and persists it) whenever it eventually completes.
+    fn recount_disk_usage_bounded(&self) -> Result<()> {
+        let root = self.path.clone();
+        let usage_path = self.path.join(usage::FILE_NAME);
+        let total = Arc::clone(&self.usage_total);
+        let approximate = Arc::clone(&self.usage_approximate);
+        // Snapshotting this lets the background thread reconcile rather
+        // than overwrite: any `put`/`del` that lands on `usage_total` while
+        // the walk is in flight (the likely case on a store with millions
+        // of files, which is exactly when the walk can outrun the
+        // timeout) must not be clobbered by a stale `computed` total.
+        let snapshot = self.usage_total.load(Ordering::SeqCst);
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            let result = usage::walk_total(&root);
+            if let Ok(computed) = &result {
+                let delta = *computed as i128 - snapshot as i128;
+                let _ = total.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                    Some((current as i128 + delta).clamp(0, u64::MAX as i128) as u64)
+                });
+                approximate.store(false, Ordering::SeqCst);
+                let _ = usage::write(&usage_path, total.load(Ordering::SeqCst), false);
+            }
+            let _ = tx.send(result);
+        });
+
+        match rx.recv_timeout(USAGE_RECOUNT_TIMEOUT) {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(err)) => Err(err),
+            Err(_) => {
+                // Didn't finish in time; leave it running in the
+                // background and persist the interim "approximate" state.
+                usage::write(&self.path.join(usage::FILE_NAME), self.disk_usage(), true)
+            }
+        }
     }
 
     fn as_path(&self, key: &str) -> PathBuf {
@@ -135,6 +572,133 @@ impl Flatfs {
     }
 }
 
+impl Drop for Flatfs {
+    fn drop(&mut self) {
+        self.flush_disk_usage();
+    }
+}
+
+/// Fsyncs a directory, so that a preceding rename into it is durable across
+/// a crash. No-op on platforms where directories can't be opened for
+/// reading (e.g. Windows), since there's nothing useful to fsync there.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    let dir = fs::File::open(dir).wrap_err_with(|| format!("Failed to open {dir:?}"))?;
+    dir.sync_all().wrap_err("Failed to fsync directory")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn remove_orphan_temp_files(dir: &Path, removed: &mut usize) -> Result<()> {
+    for entry in fs::read_dir(dir).wrap_err_with(|| format!("Failed to read dir {:?}", dir))? {
+        let path = entry
+            .wrap_err_with(|| format!("Failed to read entry in {:?}", dir))?
+            .path();
+
+        if path.is_dir() {
+            remove_orphan_temp_files(&path, removed)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(TEMP_FILE_PREFIX))
+        {
+            fs::remove_file(&path).wrap_err_with(|| format!("Failed to remove {:?}", path))?;
+            *removed += 1;
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_report(dir: &Path, root: &Path, shard: &Shard, report: &mut Report) -> Result<()> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) => {
+            report.unreadable.push(Unreadable {
+                path: dir.to_path_buf(),
+                error: err.to_string(),
+            });
+            return Ok(());
+        }
+    };
+
+    for entry in read_dir {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                report.unreadable.push(Unreadable {
+                    path: dir.to_path_buf(),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let path = entry.path();
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                report.unreadable.push(Unreadable {
+                    path,
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            collect_report(&path, root, shard, report)?;
+            continue;
+        }
+
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        if file_name.starts_with(TEMP_FILE_PREFIX) {
+            report.orphan_temp_files.push(path);
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some(EXTENSION) | Some(attr::EXTENSION)
+        ) {
+            if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+                let expected_dir = root.join(shard.dir(key));
+                if path.parent() != Some(expected_dir.as_path()) {
+                    report.misfiled.push(Misfiled {
+                        key: key.to_string(),
+                        found_at: path.clone(),
+                        expected_at: expected_dir.join(file_name),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_keys(dir: &Path, keys: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).wrap_err_with(|| format!("Failed to read dir {:?}", dir))? {
+        let path = entry
+            .wrap_err_with(|| format!("Failed to read entry in {:?}", dir))?
+            .path();
+
+        if path.is_dir() {
+            collect_keys(&path, keys)?;
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some(EXTENSION) {
+            if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn ensure_valid_key(key: &str) -> Result<()> {
     if key.len() < 2 || !key.is_ascii() || key.contains('/') {
         return Err(eyre!("Invalid key: {:?}", key));
@@ -256,4 +820,271 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_put_get_no_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::with_options(dir.path(), Shard::default(), SyncMode::None).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+        assert_eq!(flatfs.get("foo").unwrap(), b"bar");
+    }
+
+    #[test]
+    fn test_cleanup_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        let shard_dir = dir.path().join(Shard::default().dir("foo"));
+        let orphan = shard_dir.join(format!("{TEMP_FILE_PREFIX}orphan"));
+        fs::write(&orphan, b"leftover").unwrap();
+
+        assert_eq!(flatfs.cleanup_temp().unwrap(), 1);
+        assert!(!orphan.exists());
+        assert_eq!(flatfs.get("foo").unwrap(), b"bar");
+    }
+
+    #[test]
+    fn test_disk_usage_tracks_put_del() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        assert_eq!(flatfs.disk_usage(), 0);
+
+        flatfs.put("foo", [0u8; 128]).unwrap();
+        assert_eq!(flatfs.disk_usage(), 128);
+
+        // Overwriting replaces, rather than adds to, the old size.
+        flatfs.put("foo", [0u8; 64]).unwrap();
+        assert_eq!(flatfs.disk_usage(), 64);
+
+        flatfs.put("bar", [0u8; 32]).unwrap();
+        assert_eq!(flatfs.disk_usage(), 96);
+
+        flatfs.del("foo").unwrap();
+        assert_eq!(flatfs.disk_usage(), 32);
+    }
+
+    #[test]
+    fn test_disk_usage_persists_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let flatfs = Flatfs::new(dir.path()).unwrap();
+            flatfs.put("foo", [0u8; 128]).unwrap();
+        }
+
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+        assert_eq!(flatfs.disk_usage(), 128);
+    }
+
+    #[test]
+    fn test_disk_usage_tracks_attrs() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", [0u8; 128]).unwrap();
+        flatfs.put_attr("foo", 1, b"text/plain").unwrap();
+
+        // The cached, incremental total must agree with a full walk.
+        let cached = flatfs.disk_usage();
+        assert_eq!(cached, flatfs.recalculate_disk_usage().unwrap());
+        assert!(cached > 128);
+
+        flatfs.del_attr("foo", 1).unwrap();
+        assert_eq!(flatfs.disk_usage(), 128);
+
+        flatfs.del("foo").unwrap();
+        assert_eq!(flatfs.disk_usage(), 0);
+    }
+
+    #[test]
+    fn test_recalculate_disk_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", [0u8; 128]).unwrap();
+        assert_eq!(flatfs.recalculate_disk_usage().unwrap(), 128);
+    }
+
+    #[test]
+    fn test_check_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        let report = flatfs.check().unwrap();
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_check_detects_orphan_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        let shard_dir = dir.path().join(Shard::default().dir("foo"));
+        let orphan = shard_dir.join(format!("{TEMP_FILE_PREFIX}orphan"));
+        fs::write(&orphan, b"leftover").unwrap();
+
+        let report = flatfs.check().unwrap();
+        assert_eq!(report.orphan_temp_files, vec![orphan]);
+    }
+
+    #[test]
+    fn test_repair_moves_misfiled() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        // Simulate a shard-scheme change by moving the value file into the
+        // wrong shard directory by hand.
+        let correct_path = flatfs.as_path("foo");
+        let wrong_dir = dir.path().join("wrong");
+        fs::create_dir(&wrong_dir).unwrap();
+        let wrong_path = wrong_dir.join("foo.data");
+        fs::rename(&correct_path, &wrong_path).unwrap();
+
+        let report = flatfs.repair().unwrap();
+        assert_eq!(report.misfiled.len(), 1);
+        assert!(!wrong_path.exists());
+        assert_eq!(flatfs.get("foo").unwrap(), b"bar");
+        assert!(flatfs.check().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_repair_moves_misfiled_attrs() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+        flatfs.put_attr("foo", 1, b"text/plain").unwrap();
+
+        // Simulate a shard-scheme change by moving both the value and its
+        // attribute sidecar into the wrong shard directory by hand.
+        let correct_value_path = flatfs.as_path("foo");
+        let correct_attr_path = correct_value_path.with_extension("attr");
+        let wrong_dir = dir.path().join("wrong");
+        fs::create_dir(&wrong_dir).unwrap();
+        let wrong_value_path = wrong_dir.join("foo.data");
+        let wrong_attr_path = wrong_dir.join("foo.attr");
+        fs::rename(&correct_value_path, &wrong_value_path).unwrap();
+        fs::rename(&correct_attr_path, &wrong_attr_path).unwrap();
+
+        let report = flatfs.repair().unwrap();
+        assert_eq!(report.misfiled.len(), 2);
+        assert!(!wrong_attr_path.exists());
+        assert_eq!(
+            flatfs.get_attr("foo", 1).unwrap(),
+            Some(b"text/plain".to_vec())
+        );
+        assert!(flatfs.check().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_put_get_del_attr() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        assert_eq!(flatfs.get_attr("foo", 1).unwrap(), None);
+
+        flatfs.put_attr("foo", 1, b"text/plain").unwrap();
+        flatfs.put_attr("foo", 2, b"checksum").unwrap();
+
+        assert_eq!(
+            flatfs.get_attr("foo", 1).unwrap(),
+            Some(b"text/plain".to_vec())
+        );
+        assert_eq!(
+            flatfs.get_attr("foo", 2).unwrap(),
+            Some(b"checksum".to_vec())
+        );
+
+        flatfs.del_attr("foo", 1).unwrap();
+        assert_eq!(flatfs.get_attr("foo", 1).unwrap(), None);
+        assert_eq!(
+            flatfs.get_attr("foo", 2).unwrap(),
+            Some(b"checksum".to_vec())
+        );
+
+        let attr_path = dir.path().join("fo/foo.attr");
+        assert!(attr_path.exists());
+
+        flatfs.del_attr("foo", 2).unwrap();
+        assert!(!attr_path.exists());
+    }
+
+    #[test]
+    fn test_del_removes_attrs() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+        flatfs.put_attr("foo", 1, b"text/plain").unwrap();
+
+        let attr_path = dir.path().join("fo/foo.attr");
+        assert!(attr_path.exists());
+
+        flatfs.del("foo").unwrap();
+        assert!(!attr_path.exists());
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        let events = flatfs.watch();
+
+        flatfs.put("foo", b"bar").unwrap();
+        flatfs.del("foo").unwrap();
+
+        assert_eq!(
+            events.recv().unwrap(),
+            Event {
+                key: "foo".into(),
+                kind: EventKind::Added,
+            }
+        );
+        assert_eq!(
+            events.recv().unwrap(),
+            Event {
+                key: "foo".into(),
+                kind: EventKind::Removed,
+            }
+        );
+    }
+
+    #[test]
+    fn test_watch_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let flatfs = Flatfs::new(dir.path()).unwrap();
+
+        flatfs.put("foo", b"bar").unwrap();
+
+        let events = flatfs.watch_existing().unwrap();
+        flatfs.put("baz", b"qux").unwrap();
+
+        assert_eq!(
+            events.recv().unwrap(),
+            Event {
+                key: "foo".into(),
+                kind: EventKind::Added,
+            }
+        );
+        assert_eq!(
+            events.recv().unwrap(),
+            Event {
+                key: "baz".into(),
+                kind: EventKind::Added,
+            }
+        );
+    }
 }
@@ -0,0 +1,11 @@
+mod attr;
+mod check;
+mod flatfs;
+mod shard;
+mod usage;
+mod watch;
+
+pub use check::{Misfiled, Report, Unreadable};
+pub use flatfs::{Flatfs, SyncMode};
+pub use shard::Shard;
+pub use watch::{Event, EventKind, WatchStream};
@@ -0,0 +1,225 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+/// How many `AlreadyExists` results (another writer created the component
+/// first, or it was already there) a single call accepts as done before
+/// giving up with an error.
+const EXISTS_RETRIES: usize = 10;
+
+/// How many times we'll push a missing parent onto the work stack before
+/// giving up. Bounds the work in pathological cases (e.g. a path with a
+/// component that can never be created).
+const NOT_FOUND_RETRIES: usize = 10;
+
+/// Creates `path` and any missing parent directories, tolerating the races
+/// that come from multiple processes or threads creating the same nested
+/// shard directories concurrently.
+///
+/// Modeled on gix-fs's `dir::create::Iter`: we keep a stack of directories
+/// still to create, starting with the deepest one. `NotFound` means a
+/// parent is missing, so we push the current directory back on top of its
+/// parent and retry the parent first; `AlreadyExists` means either we lost
+/// a race with another creator or the directory was already there, so we
+/// treat it as done. Two separate retry budgets bound the "racing creator"
+/// case and the "missing intermediate" case independently, so two
+/// processes fighting over the same tree converge instead of spinning
+/// forever.
+pub fn create_dir_all_raced(path: &Path) -> io::Result<()> {
+    let mut to_create = vec![path.to_path_buf()];
+    let mut exists_budget = EXISTS_RETRIES;
+    let mut not_found_budget = NOT_FOUND_RETRIES;
+
+    while let Some(dir) = to_create.pop() {
+        match fs::create_dir(&dir) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                if exists_budget == 0 {
+                    return Err(err);
+                }
+                exists_budget -= 1;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                if not_found_budget == 0 {
+                    return Err(err);
+                }
+                not_found_budget -= 1;
+
+                let Some(parent) = dir.parent().map(Path::to_path_buf) else {
+                    return Err(err);
+                };
+
+                to_create.push(dir);
+                to_create.push(parent);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of the file (written at the root of the store) that records which
+/// sharding function was used to create it.
+pub const FILE_NAME: &str = "SHARDING";
+
+/// A sharding function, describing how keys are spread across
+/// subdirectories so that no single directory ends up holding an
+/// unreasonable number of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shard {
+    /// Shard by the first `n` characters of the key.
+    Prefix(usize),
+    /// Shard by the last `n` characters of the key.
+    Suffix(usize),
+    /// Shard by the `n` characters directly preceding the last character of
+    /// the key. This is the default, matching go-ipfs's flatfs datastore.
+    NextToLast(usize),
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Shard::NextToLast(2)
+    }
+}
+
+impl Shard {
+    /// Returns the shard (sub)directory that the given key belongs in,
+    /// relative to the store root.
+    pub fn dir(&self, key: &str) -> PathBuf {
+        let len = key.chars().count();
+        let name: String = match *self {
+            Shard::Prefix(n) => key.chars().take(n).collect(),
+            Shard::Suffix(n) => key.chars().skip(len.saturating_sub(n)).collect(),
+            Shard::NextToLast(n) => {
+                let end = len.saturating_sub(1);
+                let start = end.saturating_sub(n);
+                key.chars().skip(start).take(end - start).collect()
+            }
+        };
+
+        PathBuf::from(name)
+    }
+
+    /// Writes this sharding function to the `SHARDING` file at the given
+    /// store root.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file_path = path.as_ref().join(FILE_NAME);
+        fs::write(&file_path, self.to_string())
+            .wrap_err_with(|| format!("Failed to write {:?}", file_path))
+    }
+
+    /// Reads the sharding function recorded in the `SHARDING` file at the
+    /// given store root.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file_path = path.as_ref().join(FILE_NAME);
+        let contents = fs::read_to_string(&file_path)
+            .wrap_err_with(|| format!("Failed to read {:?}", file_path))?;
+
+        contents
+            .trim()
+            .parse()
+            .wrap_err_with(|| format!("Failed to parse {:?}", file_path))
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (fun, param) = match self {
+            Shard::Prefix(n) => ("prefix", n),
+            Shard::Suffix(n) => ("suffix", n),
+            Shard::NextToLast(n) => ("next-to-last", n),
+        };
+        write!(f, "/repo/flatfs/shard/v1/{fun}/{param}")
+    }
+}
+
+impl FromStr for Shard {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rest = s
+            .strip_prefix("/repo/flatfs/shard/v1/")
+            .ok_or_else(|| eyre!("Invalid shard descriptor: {:?}", s))?;
+
+        let (fun, param) = rest
+            .rsplit_once('/')
+            .ok_or_else(|| eyre!("Invalid shard descriptor: {:?}", s))?;
+
+        let param: usize = param
+            .parse()
+            .wrap_err_with(|| format!("Invalid shard parameter: {:?}", param))?;
+
+        match fun {
+            "prefix" => Ok(Shard::Prefix(param)),
+            "suffix" => Ok(Shard::Suffix(param)),
+            "next-to-last" => Ok(Shard::NextToLast(param)),
+            _ => Err(eyre!("Unknown shard function: {:?}", fun)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_display_roundtrip() {
+        for shard in [Shard::Prefix(2), Shard::Suffix(3), Shard::NextToLast(2)] {
+            let parsed: Shard = shard.to_string().parse().unwrap();
+            assert_eq!(parsed, shard);
+        }
+    }
+
+    #[test]
+    fn test_dir_next_to_last() {
+        assert_eq!(Shard::NextToLast(2).dir("foobar"), PathBuf::from("ba"));
+    }
+
+    #[test]
+    fn test_dir_prefix() {
+        assert_eq!(Shard::Prefix(2).dir("foobar"), PathBuf::from("fo"));
+    }
+
+    #[test]
+    fn test_dir_suffix() {
+        assert_eq!(Shard::Suffix(2).dir("foobar"), PathBuf::from("ar"));
+    }
+
+    #[test]
+    fn test_create_dir_all_raced_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b/c/d");
+
+        create_dir_all_raced(&nested).unwrap();
+        assert!(nested.is_dir());
+
+        // Creating it again should be a no-op, not an error.
+        create_dir_all_raced(&nested).unwrap();
+    }
+
+    #[test]
+    fn test_create_dir_all_raced_concurrent() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = Arc::new(dir.path().join("a/b/c/d"));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let nested = Arc::clone(&nested);
+                std::thread::spawn(move || create_dir_all_raced(&nested))
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        assert!(nested.is_dir());
+    }
+}
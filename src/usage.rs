@@ -0,0 +1,68 @@
+use std::{fs, path::Path};
+
+use eyre::{Result, WrapErr};
+
+use crate::{attr, flatfs};
+
+/// Name of the file (written at the root of the store) that persists the
+/// running disk-usage total, so it doesn't have to be recomputed by
+/// walking every shard directory on every open.
+pub(crate) const FILE_NAME: &str = "DISK_USAGE";
+
+/// Reads the persisted `(total_bytes, approximate)` counter, if present.
+pub(crate) fn read(path: &Path) -> Result<Option<(u64, bool)>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read(path).wrap_err_with(|| format!("Failed to read {path:?}"))?;
+    let total_bytes: [u8; 8] = data
+        .get(0..8)
+        .ok_or_else(|| eyre::eyre!("Corrupt {:?}: truncated total", path))?
+        .try_into()
+        .unwrap();
+    let approximate = *data
+        .get(8)
+        .ok_or_else(|| eyre::eyre!("Corrupt {:?}: missing approximate flag", path))?
+        != 0;
+
+    Ok(Some((u64::from_le_bytes(total_bytes), approximate)))
+}
+
+/// Persists the `(total_bytes, approximate)` counter.
+pub(crate) fn write(path: &Path, total: u64, approximate: bool) -> Result<()> {
+    let mut data = Vec::with_capacity(9);
+    data.extend_from_slice(&total.to_le_bytes());
+    data.push(approximate as u8);
+
+    fs::write(path, data).wrap_err_with(|| format!("Failed to write {path:?}"))
+}
+
+/// Computes the exact disk usage by walking every shard directory and
+/// summing the size of every value and attribute file. Ignores the
+/// `SHARDING`/`DISK_USAGE` descriptor files and any leftover temp files.
+pub(crate) fn walk_total(root: &Path) -> Result<u64> {
+    let mut total = 0;
+    walk_total_dir(root, &mut total)?;
+    Ok(total)
+}
+
+fn walk_total_dir(dir: &Path, total: &mut u64) -> Result<()> {
+    for entry in fs::read_dir(dir).wrap_err_with(|| format!("Failed to read dir {dir:?}"))? {
+        let entry = entry.wrap_err_with(|| format!("Failed to read entry in {dir:?}"))?;
+        let metadata = entry
+            .metadata()
+            .wrap_err_with(|| format!("Failed to read metadata for {:?}", entry.path()))?;
+
+        if metadata.is_dir() {
+            walk_total_dir(&entry.path(), total)?;
+        } else if matches!(
+            entry.path().extension().and_then(|ext| ext.to_str()),
+            Some(flatfs::EXTENSION) | Some(attr::EXTENSION)
+        ) {
+            *total += metadata.len();
+        }
+    }
+
+    Ok(())
+}
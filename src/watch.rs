@@ -0,0 +1,58 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// The kind of mutation a [`Event`] describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// The key was added or overwritten.
+    Added,
+    /// The key was removed.
+    Removed,
+}
+
+/// A single mutation observed on a [`crate::Flatfs`] store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub key: String,
+    pub kind: EventKind,
+}
+
+/// A stream of [`Event`]s, delivered in the order they occurred.
+pub type WatchStream = Receiver<Event>;
+
+/// The registry of currently subscribed watchers, held behind a mutex
+/// inside `Flatfs`.
+///
+/// Modeled on Fuchsia's `fatfs` `Watchers`: each subscriber gets its own
+/// channel, and a dead receiver is dropped the next time an event would
+/// have been sent to it.
+#[derive(Default)]
+pub(crate) struct Watchers {
+    senders: Vec<Sender<Event>>,
+}
+
+impl Watchers {
+    /// Registers a new, empty subscriber and returns its stream.
+    pub(crate) fn subscribe(&mut self) -> WatchStream {
+        let (tx, rx) = channel();
+        self.senders.push(tx);
+        rx
+    }
+
+    /// Registers an already-primed sender (used to deliver an "existing
+    /// keys" snapshot before live updates start flowing).
+    pub(crate) fn register(&mut self, tx: Sender<Event>) {
+        self.senders.push(tx);
+    }
+
+    /// Notifies every live subscriber of a mutation, dropping any whose
+    /// receiver has gone away.
+    pub(crate) fn notify(&mut self, key: &str, kind: EventKind) {
+        self.senders.retain(|tx| {
+            tx.send(Event {
+                key: key.to_string(),
+                kind: kind.clone(),
+            })
+            .is_ok()
+        });
+    }
+}